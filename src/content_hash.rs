@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// Per-file SHA-256 hashes, computed once and cached, so repeated queries for
+/// a file a client already has don't have to re-hash (or re-send) it. Mirrors
+/// the ETag validation pattern actix-web's `NamedFile` uses for conditional
+/// GETs.
+#[derive(Default)]
+pub struct HashCache {
+    hashes: HashMap<Uuid, String>,
+}
+
+impl HashCache {
+    pub fn hash_for(&mut self, id: Uuid, bytes: &[u8]) -> String {
+        self.hashes
+            .entry(id)
+            .or_insert_with(|| format!("{:x}", Sha256::digest(bytes)))
+            .clone()
+    }
+
+    pub fn invalidate(&mut self, id: Uuid) {
+        self.hashes.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_is_stable_for_the_same_content() {
+        let mut cache = HashCache::default();
+        let id = Uuid::new_v4();
+        let first = cache.hash_for(id, b"hello");
+        let second = cache.hash_for(id, b"hello");
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn cached_hash_ignores_later_bytes_until_invalidated() {
+        let mut cache = HashCache::default();
+        let id = Uuid::new_v4();
+        let original = cache.hash_for(id, b"hello");
+        // A cache hit returns the original hash even though the bytes differ...
+        assert_eq!(cache.hash_for(id, b"goodbye"), original);
+        // ...until the entry is invalidated, at which point it is recomputed.
+        cache.invalidate(id);
+        assert_ne!(cache.hash_for(id, b"goodbye"), original);
+    }
+
+    #[test]
+    fn different_content_hashes_differently() {
+        let mut cache = HashCache::default();
+        let a = cache.hash_for(Uuid::new_v4(), b"hello");
+        let b = cache.hash_for(Uuid::new_v4(), b"goodbye");
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::{HashMap, VecDeque};
+
+use wg_internal::network::NodeId;
+
+/// How many recently-delivered session ids to remember per peer before the
+/// oldest one is forgotten, bounding the memory a single chatty (or
+/// malicious) peer can make this hold onto.
+const SEEN_WINDOW: usize = 1024;
+
+/// Deduplicates fully-assembled messages from each peer before they reach a
+/// server's dispatch logic, the way Zed restructured its `Peer` to buffer
+/// incoming messages per connection.
+///
+/// This is deliberately dedup-only, not a reorder buffer: `session_id` is a
+/// session identifier handed out by the sender, not a dense per-peer sequence
+/// number, so there is no gap-free counter to reorder around. A `(from,
+/// session_id)` pair already delivered is dropped instead of being processed
+/// twice; anything new is dispatched immediately, in the order `handle_msg`
+/// received it (the order the underlying channel already delivers per peer).
+/// If the protocol later grows a real per-peer sequence number, this is the
+/// place to add a reorder buffer on top of the dedup below.
+#[derive(Default)]
+pub struct MessageDeduplicator {
+    seen: HashMap<NodeId, (VecDeque<u64>, std::collections::HashSet<u64>)>,
+}
+
+impl MessageDeduplicator {
+    /// Admits a freshly assembled message. Returns `Some((session_id, msg))`
+    /// if it should be dispatched now, or `None` if it's a duplicate of a
+    /// message already delivered for this peer.
+    pub fn admit(&mut self, from: NodeId, session_id: u64, msg: Vec<u8>) -> Option<(u64, Vec<u8>)> {
+        let (order, ids) = self.seen.entry(from).or_default();
+
+        if !ids.insert(session_id) {
+            return None;
+        }
+        order.push_back(session_id);
+        if order.len() > SEEN_WINDOW {
+            if let Some(evicted) = order.pop_front() {
+                ids.remove(&evicted);
+            }
+        }
+
+        Some((session_id, msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_non_contiguous_session_ids() {
+        let mut dedup = MessageDeduplicator::default();
+        assert_eq!(dedup.admit(1, 100, vec![1]), Some((100, vec![1])));
+        assert_eq!(dedup.admit(1, 205, vec![2]), Some((205, vec![2])));
+        assert_eq!(dedup.admit(1, 101, vec![3]), Some((101, vec![3])));
+    }
+
+    #[test]
+    fn drops_duplicate_session_id_from_same_peer() {
+        let mut dedup = MessageDeduplicator::default();
+        assert!(dedup.admit(1, 42, vec![1]).is_some());
+        assert_eq!(dedup.admit(1, 42, vec![1]), None);
+    }
+
+    #[test]
+    fn tracks_peers_independently() {
+        let mut dedup = MessageDeduplicator::default();
+        assert!(dedup.admit(1, 7, vec![1]).is_some());
+        assert!(dedup.admit(2, 7, vec![1]).is_some());
+    }
+
+    #[test]
+    fn evicts_oldest_session_id_once_window_is_full() {
+        let mut dedup = MessageDeduplicator::default();
+        for session_id in 0..=SEEN_WINDOW as u64 {
+            assert!(dedup.admit(1, session_id, vec![]).is_some());
+        }
+        // Admitting SEEN_WINDOW + 1 ids pushed session id 0 out of the window,
+        // so it's treated as new again.
+        assert!(dedup.admit(1, 0, vec![]).is_some());
+    }
+}
@@ -1,3 +1,13 @@
+//! This module leans on several `common::types` additions beyond what the
+//! baseline tree had: `WebResponse::{NotModified, ErrorInvalidRange,
+//! ErrorInvalidRequest, MediaChunk { media_id, offset, total_len,
+//! content_type, data }}`, `WebRequest::MediaQuery.{known_hash, offset, len}`,
+//! and `WebEvent::{CachedFiles, FileContent, TextFilesList, TextFileContent,
+//! MediaFilesList, MediaFileContent}`. `MediaFile`/`TextFile` also need
+//! `Serialize`/`DeserializeOwned` derives for `SledBackend`'s bincode path.
+//! They should land in `common` alongside this series; this tree has no
+//! `common` crate source to carry that change, so it isn't reviewable here.
+
 use std::any::Any;
 use std::collections::{HashMap};
 use crossbeam::channel::{Receiver, Sender};
@@ -7,24 +17,61 @@ use wg_internal::packet::{NodeType, Packet};
 use common::{FragmentAssembler, RoutingHandler};
 use common::packet_processor::Processor;
 use common::types::{File, MediaFile, NodeCommand, ServerType, WebCommand, WebEvent, WebRequest, WebResponse};
+use crate::content_hash::HashCache;
+use crate::mime::MimeTable;
+use crate::dedup::MessageDeduplicator;
+use crate::storage::{InMemoryBackend, SledBackend, StorageBackend};
 
-pub struct MediaServer {
+pub struct MediaServer<B: StorageBackend<MediaFile> = InMemoryBackend<MediaFile>> {
     routing_handler: RoutingHandler,
     controller_recv: Receiver<Box<dyn Any>>,
     controller_send: Sender<Box<dyn Any>>,
     packet_recv: Receiver<Packet>,
     id: NodeId,
     assembler: FragmentAssembler,
-    stored_media: HashMap<Uuid, MediaFile>,
+    stored_media: B,
+    mime_table: MimeTable,
+    content_hashes: HashCache,
+    dedup: MessageDeduplicator,
 }
 
-impl MediaServer {
+impl MediaServer<InMemoryBackend<MediaFile>> {
     pub fn new(
         id: NodeId,
         neighbors: HashMap<NodeId, Sender<Packet>>,
         packet_recv: Receiver<Packet>,
         controller_recv: Receiver<Box<dyn Any>>,
         controller_send: Sender<Box<dyn Any>>
+    ) -> Self {
+        Self::with_backend(id, neighbors, packet_recv, controller_recv, controller_send, InMemoryBackend::default())
+    }
+}
+
+impl MediaServer<SledBackend<MediaFile>> {
+    /// Like [`MediaServer::new`], but durable: media is stored in `tree` and
+    /// survives a process restart.
+    pub fn with_sled(
+        id: NodeId,
+        neighbors: HashMap<NodeId, Sender<Packet>>,
+        packet_recv: Receiver<Packet>,
+        controller_recv: Receiver<Box<dyn Any>>,
+        controller_send: Sender<Box<dyn Any>>,
+        tree: sled::Tree,
+    ) -> Self {
+        Self::with_backend(id, neighbors, packet_recv, controller_recv, controller_send, SledBackend::new(tree))
+    }
+}
+
+impl<B: StorageBackend<MediaFile>> MediaServer<B> {
+    /// Like [`MediaServer::new`], but lets the caller pick the storage backend
+    /// (e.g. a `SledBackend` for media that should survive a restart).
+    pub fn with_backend(
+        id: NodeId,
+        neighbors: HashMap<NodeId, Sender<Packet>>,
+        packet_recv: Receiver<Packet>,
+        controller_recv: Receiver<Box<dyn Any>>,
+        controller_send: Sender<Box<dyn Any>>,
+        stored_media: B,
     ) -> Self {
         let router = RoutingHandler::new(id, NodeType::Server, neighbors, controller_send.clone());
         Self {
@@ -34,32 +81,117 @@ impl MediaServer {
             packet_recv,
             id,
             assembler: FragmentAssembler::default(),
-            stored_media: HashMap::new(),
+            stored_media,
+            mime_table: MimeTable::default(),
+            content_hashes: HashCache::default(),
+            dedup: MessageDeduplicator::default(),
         }
     }
 
-    fn get_media_by_id(&self, media_id: Uuid) -> Option<&MediaFile> {
-        todo!()
+    fn get_media_by_id(&self, media_id: Uuid) -> Option<MediaFile> {
+        self.stored_media.get(media_id)
     }
 
     pub fn add_media_file(&mut self, media_file: MediaFile) {
-        todo!()
+        self.content_hashes.invalidate(media_file.id);
+        self.stored_media.put(media_file.id, media_file);
     }
 
     pub fn remove_media_file(&mut self, media_id: Uuid) -> Option<MediaFile> {
-        todo!()
+        self.content_hashes.invalidate(media_id);
+        self.stored_media.remove(media_id)
     }
 
-    fn get_all_media_files(&self) -> Vec<MediaFile> {
-        todo!()
+    fn get_media_list(&self) -> Vec<String> {
+        self.stored_media
+            .list()
+            .into_iter()
+            .map(|(id, name)| {
+                let content_type = self.mime_table.resolve(&name);
+                format!("{}:{}:{}", id, name, content_type)
+            })
+            .collect()
     }
 
-    fn get_media_list(&self) -> Vec<String> {
-        todo!()
+    /// Processes one already-ordered, already-deduplicated request. Split out
+    /// of [`Processor::handle_msg`] so the `MessageDeduplicator` gate in front
+    /// of it stays a thin wrapper.
+    fn dispatch(&mut self, msg: Vec<u8>, from: NodeId, session_id: u64) {
+        if let Ok(msg) = serde_json::from_slice::<WebRequest>(&msg) {
+            match msg {
+                WebRequest::ServerTypeQuery => {
+                    if let Ok(res) = serde_json::to_vec(&WebResponse::ServerType { server_type: ServerType::MediaServer }) {
+                        let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                    }
+                }
+
+                WebRequest::TextFilesListQuery => {}
+                WebRequest::FileQuery { .. } => {}
+                WebRequest::MediaQuery { media_id, known_hash, offset, len } => {
+                    match Uuid::parse_str(&media_id) {
+                        Ok(uuid) => {
+                            if let Some(media_file) = self.get_media_by_id(uuid) {
+                                let hash = self.content_hashes.hash_for(uuid, &media_file.data);
+                                if known_hash.as_deref() == Some(hash.as_str()) {
+                                    if let Ok(res) = serde_json::to_vec(&WebResponse::NotModified(uuid)) {
+                                        let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                                    }
+                                } else {
+                                    let total_len = media_file.data.len() as u64;
+                                    match clamp_range(total_len, offset, len) {
+                                        None => {
+                                            if let Ok(res) = serde_json::to_vec(&WebResponse::ErrorInvalidRange(uuid)) {
+                                                let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                                            }
+                                        }
+                                        Some(end) => {
+                                            let data = media_file.data[offset as usize..end as usize].to_vec();
+                                            let content_type = self.mime_table.resolve(&media_file.name);
+                                            if let Ok(res) = serde_json::to_vec(&WebResponse::MediaChunk { media_id: uuid, offset, total_len, content_type, data }) {
+                                                let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                                            }
+                                        }
+                                    }
+                                }
+                            } else {
+                                if let Ok(res) = serde_json::to_vec(&WebResponse::ErrorFileNotFound(uuid)) {
+                                    let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                                }
+                            }
+                        }
+                        Err(_) => {
+                            if let Ok(res) = serde_json::to_vec(&WebResponse::ErrorInvalidRequest(media_id)) {
+                                let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Clamps a requested `[offset, offset+len)` window to `[0, total_len]`,
+/// returning the resolved end offset, or `None` if `offset` itself is
+/// out of bounds. `len: None` means "to the end of the file".
+///
+/// `offset == total_len` is in bounds and resolves to `Some(total_len)`,
+/// i.e. an empty chunk rather than `ErrorInvalidRange` — the contract is
+/// "you asked to start exactly at end-of-stream, here is the (empty) rest
+/// of it," mirroring how an HTTP range request for `bytes=100-` against a
+/// 100-byte file gets a valid zero-length response rather than a 416. Only
+/// `offset > total_len` is out of range.
+fn clamp_range(total_len: u64, offset: u64, len: Option<u64>) -> Option<u64> {
+    if offset > total_len {
+        return None;
     }
+    Some(
+        len.map(|len| offset.saturating_add(len).min(total_len))
+            .unwrap_or(total_len),
+    )
 }
 
-impl Processor for MediaServer {
+impl<B: StorageBackend<MediaFile>> Processor for MediaServer<B> {
     fn controller_recv(&self) -> &Receiver<Box<dyn Any>> {
         &self.controller_recv
     }
@@ -77,18 +209,8 @@ impl Processor for MediaServer {
     }
 
     fn handle_msg(&mut self, msg: Vec<u8>, from: NodeId, session_id: u64) {
-        if let Ok(msg) = serde_json::from_slice::<WebRequest>(&msg) {
-            match msg {
-                WebRequest::ServerTypeQuery => {
-                    if let Ok(res) = serde_json::to_vec(&WebResponse::ServerType { server_type: ServerType::MediaServer }) {
-                        let _ = self.routing_handler.send_message(&res, from, Some(session_id));
-                    }
-                }
-
-                WebRequest::TextFilesListQuery => {}
-                WebRequest::FileQuery { .. } => {}
-                WebRequest::MediaQuery { .. } => {}
-            }
+        if let Some((session_id, msg)) = self.dedup.admit(from, session_id, msg) {
+            self.dispatch(msg, from, session_id);
         }
     }
 
@@ -101,12 +223,25 @@ impl Processor for MediaServer {
             }
         }  else if let Some(cmd) = cmd.downcast_ref::<WebCommand>() {
             match cmd {
-                WebCommand::GetCachedFiles => {}
-                WebCommand::GetFile(_) => {}
-                WebCommand::GetTextFiles => {}
-                WebCommand::GetTextFile(_) => {}
-                WebCommand::GetMediaFiles => {}
-                WebCommand::GetMediaFile(_) => {}
+                WebCommand::GetCachedFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::CachedFiles(self.get_media_list())));
+                }
+                WebCommand::GetFile(media_id) => {
+                    let content = self.get_media_by_id(*media_id).map(|file| file.data);
+                    let _ = self.controller_send.send(Box::new(WebEvent::FileContent(content)));
+                }
+                WebCommand::GetTextFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::TextFilesList(Vec::new())));
+                }
+                WebCommand::GetTextFile(_) => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::TextFileContent(None)));
+                }
+                WebCommand::GetMediaFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::MediaFilesList(self.get_media_list())));
+                }
+                WebCommand::GetMediaFile(media_id) => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::MediaFileContent(self.get_media_by_id(*media_id))));
+                }
             }
         }
         false
@@ -122,4 +257,59 @@ mod tests {
     fn test_() {
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn clamp_range_covers_whole_file_when_len_is_none() {
+        assert_eq!(clamp_range(100, 0, None), Some(100));
+    }
+
+    #[test]
+    fn clamp_range_honors_a_requested_window() {
+        assert_eq!(clamp_range(100, 10, Some(20)), Some(30));
+    }
+
+    #[test]
+    fn clamp_range_truncates_a_window_past_the_end() {
+        assert_eq!(clamp_range(100, 90, Some(50)), Some(100));
+    }
+
+    #[test]
+    fn clamp_range_allows_offset_equal_to_total_len() {
+        assert_eq!(clamp_range(100, 100, None), Some(100));
+    }
+
+    #[test]
+    fn clamp_range_rejects_offset_past_the_end() {
+        assert_eq!(clamp_range(100, 101, None), None);
+    }
+
+    #[test]
+    fn clamp_range_at_end_of_stream_yields_an_empty_chunk_not_an_error() {
+        assert_eq!(clamp_range(3, 3, None), Some(3));
+        assert_eq!(clamp_range(3, 3, Some(10)), Some(3));
+    }
+
+    fn make_server() -> MediaServer {
+        let (_packet_send, packet_recv) = unbounded();
+        let (_cmd_send, controller_recv) = unbounded();
+        let (controller_send, _cmd_recv) = unbounded();
+        MediaServer::new(1, HashMap::new(), packet_recv, controller_recv, controller_send)
+    }
+
+    #[test]
+    fn get_media_list_tags_each_entry_with_its_mime_type() {
+        let mut server = make_server();
+        let id = Uuid::new_v4();
+        server.add_media_file(MediaFile { id, name: "clip.mp4".to_string(), data: vec![1, 2, 3] });
+        assert_eq!(server.get_media_list(), vec![format!("{id}:clip.mp4:video/mp4")]);
+    }
+
+    #[test]
+    fn remove_media_file_drops_it_from_the_list() {
+        let mut server = make_server();
+        let id = Uuid::new_v4();
+        server.add_media_file(MediaFile { id, name: "clip.mp4".to_string(), data: vec![1, 2, 3] });
+        server.remove_media_file(id);
+        assert!(server.get_media_list().is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+/// Returned for any file whose extension isn't in the loaded table.
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+const MIME_TABLE_SRC: &str = include_str!("mime.types");
+
+/// Extension -> MIME type lookup, parsed once from a `mime.types`-style table
+/// (one type followed by its extensions per line, `#` starts a comment), the
+/// same format syndicate's `load_mime_table` reads.
+pub struct MimeTable {
+    by_extension: HashMap<String, String>,
+}
+
+impl MimeTable {
+    pub fn parse(table: &str) -> Self {
+        let mut by_extension = HashMap::new();
+        for line in table.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            if let Some(mime_type) = fields.next() {
+                for ext in fields {
+                    by_extension.insert(ext.to_ascii_lowercase(), mime_type.to_string());
+                }
+            }
+        }
+        Self { by_extension }
+    }
+
+    /// Resolves the MIME type for a file name by its extension, falling back
+    /// to [`DEFAULT_CONTENT_TYPE`] when the extension is unknown or missing.
+    pub fn resolve(&self, file_name: &str) -> String {
+        file_name
+            .rsplit('.')
+            .next()
+            .filter(|ext| *ext != file_name)
+            .and_then(|ext| self.by_extension.get(&ext.to_ascii_lowercase()))
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string())
+    }
+}
+
+impl Default for MimeTable {
+    fn default() -> Self {
+        Self::parse(MIME_TABLE_SRC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLE: &str = "\
+# a comment line, and a blank line below
+
+image/png png
+video/mp4 mp4 m4v
+";
+
+    #[test]
+    fn resolves_known_extension() {
+        let table = MimeTable::parse(TABLE);
+        assert_eq!(table.resolve("photo.png"), "image/png");
+    }
+
+    #[test]
+    fn resolves_case_insensitively() {
+        let table = MimeTable::parse(TABLE);
+        assert_eq!(table.resolve("PHOTO.PNG"), "image/png");
+    }
+
+    #[test]
+    fn one_mime_type_can_have_several_extensions() {
+        let table = MimeTable::parse(TABLE);
+        assert_eq!(table.resolve("clip.mp4"), "video/mp4");
+        assert_eq!(table.resolve("clip.m4v"), "video/mp4");
+    }
+
+    #[test]
+    fn falls_back_to_default_for_unknown_extension() {
+        let table = MimeTable::parse(TABLE);
+        assert_eq!(table.resolve("archive.zip"), DEFAULT_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn falls_back_to_default_when_there_is_no_extension() {
+        let table = MimeTable::parse(TABLE);
+        assert_eq!(table.resolve("README"), DEFAULT_CONTENT_TYPE);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let table = MimeTable::parse(TABLE);
+        // Had the comment line been parsed, "comment" would map to "#".
+        assert_eq!(table.resolve("file.comment"), DEFAULT_CONTENT_TYPE);
+    }
+}
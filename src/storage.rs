@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+
+use common::types::{MediaFile, TextFile};
+
+/// A pluggable persistence layer for the content a server hands back to clients.
+///
+/// Mirrors the `Backend` trait from the sftp-server crate: servers stay generic
+/// over how a file actually gets stored, so an operator can swap an ephemeral
+/// in-memory map for a durable store (see [`SledBackend`]) without touching any
+/// `Processor` dispatch logic.
+pub trait StorageBackend<T>: Send {
+    fn get(&self, id: Uuid) -> Option<T>;
+    fn put(&mut self, id: Uuid, file: T);
+    fn remove(&mut self, id: Uuid) -> Option<T>;
+    fn list(&self) -> Vec<(Uuid, String)>;
+}
+
+/// Gives a stored type the display name used in `list()` results, without
+/// requiring `StorageBackend` itself to know anything about file layout.
+pub trait Named {
+    fn display_name(&self) -> String;
+}
+
+impl Named for TextFile {
+    fn display_name(&self) -> String {
+        self.title.clone()
+    }
+}
+
+impl Named for MediaFile {
+    fn display_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// The default backend: everything lives in a `HashMap` and is lost on restart.
+pub struct InMemoryBackend<T> {
+    entries: HashMap<Uuid, T>,
+}
+
+// Written by hand instead of `#[derive(Default)]`: the derive would add a
+// spurious `T: Default` bound, but an empty map doesn't need one.
+impl<T> Default for InMemoryBackend<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Named + Clone + Send> StorageBackend<T> for InMemoryBackend<T> {
+    fn get(&self, id: Uuid) -> Option<T> {
+        self.entries.get(&id).cloned()
+    }
+
+    fn put(&mut self, id: Uuid, file: T) {
+        self.entries.insert(id, file);
+    }
+
+    fn remove(&mut self, id: Uuid) -> Option<T> {
+        self.entries.remove(&id)
+    }
+
+    fn list(&self) -> Vec<(Uuid, String)> {
+        self.entries
+            .iter()
+            .map(|(id, file)| (*id, file.display_name()))
+            .collect()
+    }
+}
+
+/// A durable backend built on a `sled::Tree`, the same way velocimeter's
+/// `FileCache` persists entries: one tree, keyed by the file's `Uuid`, so
+/// content survives a node restart.
+pub struct SledBackend<T> {
+    tree: sled::Tree,
+    _marker: PhantomData<T>,
+}
+
+impl<T> SledBackend<T> {
+    pub fn new(tree: sled::Tree) -> Self {
+        Self {
+            tree,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Named + Serialize + DeserializeOwned + Send> StorageBackend<T> for SledBackend<T> {
+    fn get(&self, id: Uuid) -> Option<T> {
+        let bytes = self.tree.get(id.as_bytes()).ok().flatten()?;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    fn put(&mut self, id: Uuid, file: T) {
+        if let Ok(bytes) = bincode::serialize(&file) {
+            let _ = self.tree.insert(id.as_bytes(), bytes);
+        }
+    }
+
+    fn remove(&mut self, id: Uuid) -> Option<T> {
+        let bytes = self.tree.remove(id.as_bytes()).ok().flatten()?;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    fn list(&self) -> Vec<(Uuid, String)> {
+        self.tree
+            .iter()
+            .filter_map(Result::ok)
+            .filter_map(|(key, value)| {
+                let id = Uuid::from_slice(&key).ok()?;
+                let file: T = bincode::deserialize(&value).ok()?;
+                Some((id, file.display_name()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Serialize, serde::Deserialize)]
+    struct Stub(String);
+
+    impl Named for Stub {
+        fn display_name(&self) -> String {
+            self.0.clone()
+        }
+    }
+
+    /// A fresh, on-disk-backed but auto-deleted sled tree, so each test gets
+    /// its own isolated store without leaving files behind.
+    fn temp_tree() -> sled::Tree {
+        sled::Config::new()
+            .temporary(true)
+            .open()
+            .expect("open temp sled db")
+            .open_tree("test")
+            .expect("open tree")
+    }
+
+    #[test]
+    fn in_memory_backend_is_empty_by_default() {
+        let backend: InMemoryBackend<Stub> = InMemoryBackend::default();
+        assert!(backend.list().is_empty());
+    }
+
+    #[test]
+    fn put_then_get_round_trips() {
+        let mut backend = InMemoryBackend::default();
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.get(id).map(|s| s.0), Some("a.txt".to_string()));
+    }
+
+    #[test]
+    fn list_reflects_stored_entries() {
+        let mut backend = InMemoryBackend::default();
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.list(), vec![(id, "a.txt".to_string())]);
+    }
+
+    #[test]
+    fn remove_takes_the_entry_out() {
+        let mut backend = InMemoryBackend::default();
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.remove(id).map(|s| s.0), Some("a.txt".to_string()));
+        assert!(backend.get(id).is_none());
+        assert!(backend.list().is_empty());
+    }
+
+    #[test]
+    fn get_on_missing_id_is_none() {
+        let backend: InMemoryBackend<Stub> = InMemoryBackend::default();
+        assert!(backend.get(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn sled_backend_put_then_get_round_trips() {
+        let mut backend = SledBackend::new(temp_tree());
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.get(id).map(|s| s.0), Some("a.txt".to_string()));
+    }
+
+    #[test]
+    fn sled_backend_list_reflects_stored_entries() {
+        let mut backend = SledBackend::new(temp_tree());
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.list(), vec![(id, "a.txt".to_string())]);
+    }
+
+    #[test]
+    fn sled_backend_remove_takes_the_entry_out() {
+        let mut backend = SledBackend::new(temp_tree());
+        let id = Uuid::new_v4();
+        backend.put(id, Stub("a.txt".to_string()));
+        assert_eq!(backend.remove(id).map(|s| s.0), Some("a.txt".to_string()));
+        assert!(backend.get(id).is_none());
+        assert!(backend.list().is_empty());
+    }
+}
@@ -1,3 +1,12 @@
+//! This module leans on several `common::types` additions beyond what the
+//! baseline tree had: `WebResponse::{NotModified, ErrorInvalidRequest}`,
+//! `WebRequest::FileQuery.known_hash`, and `WebEvent::{CachedFiles,
+//! FileContent, TextFilesList, TextFileContent, MediaFilesList,
+//! MediaFileContent}` all carrying `Vec<String>`/`Option<...>` payloads
+//! rather than raw `(Uuid, String)` tuples. They should land in `common`
+//! alongside this series; this tree has no `common` crate source to carry
+//! that change, so it isn't reviewable here.
+
 use std::any::Any;
 use std::collections::{HashMap};
 use crossbeam::channel::{Receiver, Sender};
@@ -6,25 +15,60 @@ use wg_internal::network::NodeId;
 use wg_internal::packet::{NodeType, Packet};
 use common::{FragmentAssembler, RoutingHandler};
 use common::packet_processor::Processor;
-use common::types::{NodeCommand, ServerType, TextFile, WebCommand, WebRequest, WebResponse};
+use common::types::{NodeCommand, ServerType, TextFile, WebCommand, WebEvent, WebRequest, WebResponse};
+use crate::content_hash::HashCache;
+use crate::dedup::MessageDeduplicator;
+use crate::storage::{InMemoryBackend, SledBackend, StorageBackend};
 
-pub struct TextServer {
+pub struct TextServer<B: StorageBackend<TextFile> = InMemoryBackend<TextFile>> {
     routing_handler: RoutingHandler,
     controller_recv: Receiver<Box<dyn Any>>,
     controller_send: Sender<Box<dyn Any>>,
     packet_recv: Receiver<Packet>,
     id: NodeId,
     assembler: FragmentAssembler,
-    stored_files: HashMap<Uuid, TextFile>,
+    stored_files: B,
+    content_hashes: HashCache,
+    dedup: MessageDeduplicator,
 }
 
-impl TextServer {
+impl TextServer<InMemoryBackend<TextFile>> {
     pub fn new(
         id: NodeId,
         neighbors: HashMap<NodeId, Sender<Packet>>,
         packet_recv: Receiver<Packet>,
         controller_recv: Receiver<Box<dyn Any>>,
         controller_send: Sender<Box<dyn Any>>
+    ) -> Self {
+        Self::with_backend(id, neighbors, packet_recv, controller_recv, controller_send, InMemoryBackend::default())
+    }
+}
+
+impl TextServer<SledBackend<TextFile>> {
+    /// Like [`TextServer::new`], but durable: content is stored in `tree` and
+    /// survives a process restart.
+    pub fn with_sled(
+        id: NodeId,
+        neighbors: HashMap<NodeId, Sender<Packet>>,
+        packet_recv: Receiver<Packet>,
+        controller_recv: Receiver<Box<dyn Any>>,
+        controller_send: Sender<Box<dyn Any>>,
+        tree: sled::Tree,
+    ) -> Self {
+        Self::with_backend(id, neighbors, packet_recv, controller_recv, controller_send, SledBackend::new(tree))
+    }
+}
+
+impl<B: StorageBackend<TextFile>> TextServer<B> {
+    /// Like [`TextServer::new`], but lets the caller pick the storage backend
+    /// (e.g. a `SledBackend` for content that should survive a restart).
+    pub fn with_backend(
+        id: NodeId,
+        neighbors: HashMap<NodeId, Sender<Packet>>,
+        packet_recv: Receiver<Packet>,
+        controller_recv: Receiver<Box<dyn Any>>,
+        controller_send: Sender<Box<dyn Any>>,
+        stored_files: B,
     ) -> Self {
         let router = RoutingHandler::new(id, NodeType::Server, neighbors, controller_send.clone());
         Self {
@@ -34,40 +78,28 @@ impl TextServer {
             packet_recv,
             id,
             assembler: FragmentAssembler::default(),
-            stored_files: HashMap::new(),
+            stored_files,
+            content_hashes: HashCache::default(),
+            dedup: MessageDeduplicator::default(),
         }
     }
 
     fn get_files_list(&self) -> Vec<String> {
         self.stored_files
-            .values()
-            .map(|file| format!("{}:{}", file.id, file.title))
+            .list()
+            .into_iter()
+            .map(|(id, title)| format!("{}:{}", id, title))
             .collect()
     }
 
-    pub fn get_file_by_id(&self, file_id: Uuid) -> Option<&TextFile> {
-        self.stored_files.get(&file_id)
-    }
-}
-
-impl Processor for TextServer {
-    fn controller_recv(&self) -> &Receiver<Box<dyn Any>> {
-        &self.controller_recv
-    }
-
-    fn packet_recv(&self) -> &Receiver<Packet> {
-        &self.packet_recv
-    }
-
-    fn assembler(&mut self) -> &mut FragmentAssembler {
-        &mut self.assembler
-    }
-
-    fn routing_handler(&mut self) -> &mut RoutingHandler {
-        &mut self.routing_handler
+    pub fn get_file_by_id(&self, file_id: Uuid) -> Option<TextFile> {
+        self.stored_files.get(file_id)
     }
 
-    fn handle_msg(&mut self, msg: Vec<u8>, from: NodeId, session_id: u64) {
+    /// Processes one already-ordered, already-deduplicated request. Split out
+    /// of [`Processor::handle_msg`] so the `MessageDeduplicator` gate in front
+    /// of it stays a thin wrapper.
+    fn dispatch(&mut self, msg: Vec<u8>, from: NodeId, session_id: u64) {
         if let Ok(msg) = serde_json::from_slice::<WebRequest>(&msg) {
             match msg {
                 WebRequest::ServerTypeQuery => {
@@ -81,12 +113,20 @@ impl Processor for TextServer {
                         let _ = self.routing_handler.send_message(&res, from, Some(session_id));
                     }
                 }
-                WebRequest::FileQuery { file_id } => {
+                WebRequest::FileQuery { file_id, known_hash } => {
                     match Uuid::parse_str(&file_id) {
                         Ok(uuid) => {
                             if let Some(text_file) = self.get_file_by_id(uuid) {
-                                if let Ok(serialized_file) = serde_json::to_vec(text_file) {
-                                    if let Ok(res) = serde_json::to_vec(&WebResponse::TextFile { file_data: serialized_file }) {
+                                if let Ok(serialized_file) = serde_json::to_vec(&text_file) {
+                                    // Hash the bytes we actually ship (the full `TextFile`,
+                                    // not just `content`), so a client hashing the payload it
+                                    // received can produce a `known_hash` that matches.
+                                    let hash = self.content_hashes.hash_for(uuid, &serialized_file);
+                                    if known_hash.as_deref() == Some(hash.as_str()) {
+                                        if let Ok(res) = serde_json::to_vec(&WebResponse::NotModified(uuid)) {
+                                            let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                                        }
+                                    } else if let Ok(res) = serde_json::to_vec(&WebResponse::TextFile { file_data: serialized_file }) {
                                         let _ = self.routing_handler.send_message(&res, from, Some(session_id));
                                     }
                                 }
@@ -97,18 +137,45 @@ impl Processor for TextServer {
                             }
                         }
                         Err(_) => {
-                            // eprintln!("Invalid UUID format in file query: {}", file_id);
-                            todo!()
+                            if let Ok(res) = serde_json::to_vec(&WebResponse::ErrorInvalidRequest(file_id)) {
+                                let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                            }
                         }
                     }
                 }
                 WebRequest::MediaQuery { .. } => {
-                    // eprintln!("Text server received media query - this should be handled by media server");
-                    todo!();
+                    // A text server has no media to serve; tell the client rather than panic.
+                    if let Ok(res) = serde_json::to_vec(&WebResponse::ErrorInvalidRequest("media queries are not served by a text server".to_string())) {
+                        let _ = self.routing_handler.send_message(&res, from, Some(session_id));
+                    }
                 }
             }
         }
     }
+}
+
+impl<B: StorageBackend<TextFile>> Processor for TextServer<B> {
+    fn controller_recv(&self) -> &Receiver<Box<dyn Any>> {
+        &self.controller_recv
+    }
+
+    fn packet_recv(&self) -> &Receiver<Packet> {
+        &self.packet_recv
+    }
+
+    fn assembler(&mut self) -> &mut FragmentAssembler {
+        &mut self.assembler
+    }
+
+    fn routing_handler(&mut self) -> &mut RoutingHandler {
+        &mut self.routing_handler
+    }
+
+    fn handle_msg(&mut self, msg: Vec<u8>, from: NodeId, session_id: u64) {
+        if let Some((session_id, msg)) = self.dedup.admit(from, session_id, msg) {
+            self.dispatch(msg, from, session_id);
+        }
+    }
 
     fn handle_command(&mut self, cmd: Box<dyn Any>) -> bool {
         if let Some(cmd) = cmd.downcast_ref::<NodeCommand>() {
@@ -119,15 +186,27 @@ impl Processor for TextServer {
             }
         }  else if let Some(cmd) = cmd.downcast_ref::<WebCommand>() {
             match cmd {
-                WebCommand::GetCachedFiles => {todo!()}
-                WebCommand::GetFile(_) => {todo!()}
-                WebCommand::GetTextFiles => {todo!()}
-                WebCommand::GetTextFile(_) => {todo!()}
-                WebCommand::GetMediaFiles => {todo!()}
-                WebCommand::GetMediaFile(_) => {todo!()}
+                WebCommand::GetCachedFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::CachedFiles(self.get_files_list())));
+                }
+                WebCommand::GetFile(file_id) => {
+                    let content = self.get_file_by_id(*file_id).map(|file| file.content.into_bytes());
+                    let _ = self.controller_send.send(Box::new(WebEvent::FileContent(content)));
+                }
+                WebCommand::GetTextFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::TextFilesList(self.get_files_list())));
+                }
+                WebCommand::GetTextFile(file_id) => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::TextFileContent(self.get_file_by_id(*file_id))));
+                }
+                WebCommand::GetMediaFiles => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::MediaFilesList(Vec::new())));
+                }
+                WebCommand::GetMediaFile(_) => {
+                    let _ = self.controller_send.send(Box::new(WebEvent::MediaFileContent(None)));
+                }
             }
         }
         false
     }
 }
-